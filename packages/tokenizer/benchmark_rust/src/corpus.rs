@@ -0,0 +1,77 @@
+//! Memory-mapped corpus loading for training on large files.
+//!
+//! `train_from_file` never loads the whole corpus into a `String`: it
+//! memory-maps the file, splits it into roughly-even chunks aligned to line
+//! boundaries, and counts words within each chunk in parallel before folding
+//! the partial counts together -- the same map/reduce shape
+//! `trainer::count_pairs_parallel` already uses.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::pretokenizer::PreTokenizer;
+
+/// Splits `data` into byte ranges of roughly `data.len() / num_chunks` bytes
+/// each, pushed forward to the next newline so no chunk cuts a line in half.
+fn line_aligned_chunks(data: &[u8], num_chunks: usize) -> Vec<&[u8]> {
+    if data.is_empty() || num_chunks <= 1 {
+        return vec![data];
+    }
+
+    let approx_len = data.len() / num_chunks;
+    let mut chunks = Vec::with_capacity(num_chunks);
+    let mut start = 0;
+    while start < data.len() {
+        let mut end = (start + approx_len).min(data.len());
+        if end < data.len() {
+            end += data[end..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(data.len() - end, |p| p + 1);
+        }
+        chunks.push(&data[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+/// Memory-maps `path` and builds word counts from it in parallel, using
+/// `pre_tokenizer` to split each chunk the same way training from an
+/// in-memory corpus does. Feed the result into `trainer::words_from_counts`.
+pub fn train_from_file(
+    path: impl AsRef<Path>,
+    pre_tokenizer: &PreTokenizer,
+) -> io::Result<HashMap<String, i32>> {
+    let file = File::open(path)?;
+    // Safety: the mapped file must not be modified by another process for the
+    // lifetime of this mapping; that's the standard caveat of memory-mapped
+    // I/O and is the caller's responsibility here.
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    let num_chunks = rayon::current_num_threads().max(1) * 4;
+    let chunks = line_aligned_chunks(&mmap, num_chunks);
+
+    let word_counts = chunks
+        .par_iter()
+        .map(|chunk| {
+            let text = String::from_utf8_lossy(chunk);
+            let mut local: HashMap<String, i32> = HashMap::new();
+            for word in pre_tokenizer.split(&text) {
+                *local.entry(word.to_string()).or_default() += 1;
+            }
+            local
+        })
+        .reduce(HashMap::new, |mut acc, local| {
+            for (k, v) in local {
+                *acc.entry(k).or_default() += v;
+            }
+            acc
+        });
+
+    Ok(word_counts)
+}
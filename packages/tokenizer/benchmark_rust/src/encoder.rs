@@ -0,0 +1,93 @@
+//! Rank-based BPE encoding.
+//!
+//! Given the merges learned by the trainer, encoding repeatedly finds the
+//! *lowest-rank* adjacent pair still present in the token stream and merges
+//! every occurrence of it, until no known pair remains. This is the standard
+//! minimal-BPE encode loop: merges are applied in the order they were
+//! learned, and a `HashMap<Pair, u32>` rank lookup means no merge ever has to
+//! re-derive its own id by scanning the merge list.
+
+use std::collections::HashMap;
+
+use crate::pretokenizer::PreTokenizer;
+use crate::trainer::ByteFallback;
+use crate::word::Pair;
+
+/// Maps a learned pair to its merge rank (== its id minus the reserved
+/// special-token ids and the 256 base bytes).
+pub fn build_merge_ranks(merges: &[Pair]) -> HashMap<Pair, u32> {
+    merges
+        .iter()
+        .enumerate()
+        .map(|(rank, &pair)| (pair, rank as u32))
+        .collect()
+}
+
+/// Merges every occurrence of `pair` in `tokens` into `new_id`.
+fn merge_all(tokens: &[u32], pair: Pair, new_id: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        if i + 1 < tokens.len() && tokens[i] == pair.0 && tokens[i + 1] == pair.1 {
+            out.push(new_id);
+            i += 2;
+        } else {
+            out.push(tokens[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Encodes a single pre-token (a byte run with no internal whitespace/
+/// punctuation boundary) by repeatedly merging its lowest-rank pair.
+/// `base_offset` shifts the 256 base-byte ids past any reserved special
+/// tokens, matching the ids the trainer assigned. `byte_fallback`, if set,
+/// folds any byte outside its kept set into its fallback id instead of
+/// `base_offset + byte`, matching whatever `limit_alphabet` the tokenizer was
+/// trained with (see [`crate::trainer::words_from_counts`]); a byte that was
+/// never in the alphabet the trainer saw took part in no learned merges, so
+/// encoding it as its own raw id would disagree with training.
+fn encode_pre_token(
+    pre_token: &str,
+    merge_ranks: &HashMap<Pair, u32>,
+    base_offset: u32,
+    byte_fallback: Option<&ByteFallback>,
+) -> Vec<u32> {
+    let mut tokens: Vec<u32> = pre_token
+        .bytes()
+        .map(|b| match byte_fallback {
+            Some((kept, fallback_id)) if !kept.contains(&b) => *fallback_id,
+            _ => base_offset + b as u32,
+        })
+        .collect();
+
+    loop {
+        let best = tokens
+            .windows(2)
+            .filter_map(|w| merge_ranks.get(&(w[0], w[1])).map(|&rank| (w[0], w[1], rank)))
+            .min_by_key(|&(_, _, rank)| rank);
+
+        let Some((a, b, rank)) = best else {
+            break;
+        };
+        tokens = merge_all(&tokens, (a, b), base_offset + 256 + rank);
+    }
+
+    tokens
+}
+
+/// Encodes `text` into BPE token ids, pre-tokenizing first so merges never
+/// cross a pre-token boundary.
+pub fn encode(
+    text: &str,
+    pre_tokenizer: &PreTokenizer,
+    merge_ranks: &HashMap<Pair, u32>,
+    base_offset: u32,
+    byte_fallback: Option<&ByteFallback>,
+) -> Vec<u32> {
+    pre_tokenizer
+        .split(text)
+        .flat_map(|pre_token| encode_pre_token(pre_token, merge_ranks, base_offset, byte_fallback))
+        .collect()
+}
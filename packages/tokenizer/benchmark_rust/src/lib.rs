@@ -0,0 +1,15 @@
+//! Minimal Rust BPE, simplified from nanochat's rustbpe.
+//!
+//! The binary in `main.rs` benchmarks training and encoding; the pieces it
+//! exercises are public here so a trained [`Tokenizer`] can be built, saved,
+//! and reused outside of the benchmark.
+
+pub mod corpus;
+pub mod encoder;
+pub mod pretokenizer;
+pub mod tokenizer;
+pub mod trainer;
+pub mod word;
+
+pub use pretokenizer::PreTokenizer;
+pub use tokenizer::Tokenizer;
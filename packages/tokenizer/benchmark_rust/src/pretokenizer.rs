@@ -0,0 +1,55 @@
+//! Regex pre-tokenization, GPT-2/GPT-4 style.
+//!
+//! Splitting on raw whitespace lets BPE merges cross punctuation, contraction,
+//! and number boundaries, which produces worse merges and makes every `Word`
+//! as long as the corpus allows. Pre-tokenizing first keeps merges inside a
+//! pre-token and bounds how much work each `Word` does.
+
+use fancy_regex::Regex;
+
+/// The classic GPT-2 split: contractions, runs of letters, runs of digits,
+/// runs of other non-space symbols, then trailing whitespace.
+pub const GPT2_PATTERN: &str =
+    r"'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+";
+
+/// The GPT-4 / cl100k split: same shape, but contractions are case-insensitive
+/// and digit runs are grouped into chunks of at most 3.
+pub const GPT4_PATTERN: &str =
+    r"(?i:'s|'t|'re|'ve|'m|'ll|'d)| ?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+/// A compiled pre-tokenization pattern. Swap in `GPT4_PATTERN` (or any custom
+/// pattern) via `PreTokenizer::new` to change splitting behavior without
+/// touching the training or encoding loops.
+pub struct PreTokenizer {
+    pattern: Regex,
+}
+
+impl PreTokenizer {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: Regex::new(pattern).expect("pre-tokenizer pattern should compile"),
+        }
+    }
+
+    pub fn gpt2() -> Self {
+        Self::new(GPT2_PATTERN)
+    }
+
+    pub fn gpt4() -> Self {
+        Self::new(GPT4_PATTERN)
+    }
+
+    /// Splits `text` into pre-tokens. BPE merges never cross a boundary here.
+    pub fn split<'t>(&'t self, text: &'t str) -> impl Iterator<Item = &'t str> + 't {
+        self.pattern
+            .find_iter(text)
+            .filter_map(|m| m.ok())
+            .map(|m| m.as_str())
+    }
+}
+
+impl Default for PreTokenizer {
+    fn default() -> Self {
+        Self::gpt2()
+    }
+}
@@ -0,0 +1,303 @@
+//! A trained, reusable tokenizer.
+//!
+//! `Tokenizer` is the public surface a caller actually wants: the merge
+//! table and vocabulary produced by the trainer, a ready-to-use rank map for
+//! the encoder, and `save`/`load` so a trained tokenizer survives past the
+//! process that trained it.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::encoder::{self, build_merge_ranks};
+use crate::pretokenizer::PreTokenizer;
+use crate::trainer::ByteFallback;
+use crate::word::Pair;
+
+#[derive(Serialize, Deserialize)]
+pub struct Tokenizer {
+    /// Learned merges in training order; a pair's rank is its index here.
+    merges: Vec<Pair>,
+    /// id -> raw bytes, covering the special tokens' text, then the 256 base
+    /// bytes, then one entry per merge (the concatenation of its two inputs'
+    /// bytes).
+    vocab: Vec<Vec<u8>>,
+    /// Special tokens reserved before the byte base, in id order.
+    special_tokens: Vec<String>,
+    /// The `(kept bytes, fallback_id)` a `limit_alphabet`-trained merge table
+    /// expects at encode time, or `None` if the tokenizer was trained without
+    /// a cap -- see [`trainer::words_from_counts`](crate::trainer::words_from_counts),
+    /// which computes the same set during training.
+    byte_fallback: Option<ByteFallback>,
+
+    #[serde(skip)]
+    merge_ranks: HashMap<Pair, u32>,
+}
+
+impl Tokenizer {
+    /// Builds a `Tokenizer` from merges learned by a [`BpeTrainer`](crate::trainer::BpeTrainer)
+    /// configured with the same `special_tokens`. `byte_fallback` must be the
+    /// same value `words_from_counts` returned when building the words that
+    /// merges were learned from, so encoding folds excluded bytes the same
+    /// way training did.
+    pub fn new(merges: Vec<Pair>, special_tokens: Vec<String>, byte_fallback: Option<ByteFallback>) -> Self {
+        let mut vocab: Vec<Vec<u8>> = Vec::with_capacity(special_tokens.len() + 256 + merges.len());
+        vocab.extend(special_tokens.iter().map(|t| t.as_bytes().to_vec()));
+        vocab.extend((0..256u32).map(|b| vec![b as u8]));
+        for &(a, b) in &merges {
+            let mut bytes = vocab[a as usize].clone();
+            bytes.extend_from_slice(&vocab[b as usize]);
+            vocab.push(bytes);
+        }
+        let merge_ranks = build_merge_ranks(&merges);
+
+        Self { merges, vocab, special_tokens, byte_fallback, merge_ranks }
+    }
+
+    pub fn merges(&self) -> &[Pair] {
+        &self.merges
+    }
+
+    pub fn vocab(&self) -> &[Vec<u8>] {
+        &self.vocab
+    }
+
+    /// Id of the first of the 256 base-byte tokens, after the reserved
+    /// special tokens.
+    pub fn base_offset(&self) -> u32 {
+        self.special_tokens.len() as u32
+    }
+
+    /// Encodes one chunk of ordinary (non-special-token) text.
+    fn encode_chunk(&self, text: &str, pre_tokenizer: &PreTokenizer) -> Vec<u32> {
+        encoder::encode(text, pre_tokenizer, &self.merge_ranks, self.base_offset(), self.byte_fallback.as_ref())
+    }
+
+    /// Encodes `text` using this tokenizer's learned merges. Any occurrence of
+    /// a special token is emitted atomically as its reserved id, without
+    /// being pre-tokenized or merged.
+    pub fn encode(&self, text: &str, pre_tokenizer: &PreTokenizer) -> Vec<u32> {
+        split_on_special_tokens(text, &self.special_tokens)
+            .into_iter()
+            .flat_map(|chunk| match chunk {
+                Chunk::Special(id) => vec![id],
+                Chunk::Text(text) => self.encode_chunk(text, pre_tokenizer),
+            })
+            .collect()
+    }
+
+    /// Counts how many tokens `text` would encode to. Each chunk is still
+    /// encoded into its own `Vec<u32>` internally (the BPE merge loop needs a
+    /// working buffer), but unlike `encode`, those per-chunk buffers are
+    /// dropped as soon as they're counted instead of being collected into one
+    /// `Vec<u32>` spanning the whole text.
+    pub fn count_tokens(&self, text: &str, pre_tokenizer: &PreTokenizer) -> usize {
+        split_on_special_tokens(text, &self.special_tokens)
+            .into_iter()
+            .map(|chunk| match chunk {
+                Chunk::Special(_) => 1,
+                Chunk::Text(text) => self.encode_chunk(text, pre_tokenizer).len(),
+            })
+            .sum()
+    }
+
+    /// Encodes `text` up to `max_tokens`, mirroring the max-tokens guard chat
+    /// frontends use to warn before a request overflows a model's context
+    /// window. Encodes chunk by chunk (a special token, or the text between
+    /// special tokens); when the next chunk would push the count past
+    /// `max_tokens`, keeps only as many of its leading tokens as still fit and
+    /// stops, reporting whether it had to truncate and how much of the budget
+    /// is left.
+    pub fn encode_with_limit(
+        &self,
+        text: &str,
+        pre_tokenizer: &PreTokenizer,
+        max_tokens: usize,
+    ) -> LimitedEncoding {
+        let mut tokens = Vec::new();
+        let mut truncated = false;
+
+        for chunk in split_on_special_tokens(text, &self.special_tokens) {
+            let chunk_tokens = match chunk {
+                Chunk::Special(id) => vec![id],
+                Chunk::Text(text) => self.encode_chunk(text, pre_tokenizer),
+            };
+
+            let room = max_tokens - tokens.len();
+            if chunk_tokens.len() > room {
+                tokens.extend_from_slice(&chunk_tokens[..room]);
+                truncated = true;
+                break;
+            }
+            tokens.extend(chunk_tokens);
+        }
+
+        let remaining = max_tokens - tokens.len();
+        LimitedEncoding { tokens, remaining, truncated }
+    }
+
+    /// Writes the merge table, vocabulary, and special tokens to `path` as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self).map_err(io::Error::other)
+    }
+
+    /// Loads a tokenizer previously written by `save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut tokenizer: Self =
+            serde_json::from_reader(BufReader::new(file)).map_err(io::Error::other)?;
+        tokenizer.merge_ranks = build_merge_ranks(&tokenizer.merges);
+        Ok(tokenizer)
+    }
+}
+
+/// The result of [`Tokenizer::encode_with_limit`].
+pub struct LimitedEncoding {
+    /// The tokens that fit within the budget.
+    pub tokens: Vec<u32>,
+    /// How much of `max_tokens` is left unused; `0` if `truncated`.
+    pub remaining: usize,
+    /// Whether `text` had more tokens than `max_tokens` allowed.
+    pub truncated: bool,
+}
+
+enum Chunk<'t> {
+    /// A literal special-token match, carrying its reserved id.
+    Special(u32),
+    /// Ordinary text still to be pre-tokenized and BPE-encoded.
+    Text(&'t str),
+}
+
+/// Splits `text` around every occurrence of a special token, leftmost match
+/// first and longest match on a tie, so special tokens survive encoding
+/// intact instead of being pre-tokenized and merged like ordinary text.
+fn split_on_special_tokens<'t>(text: &'t str, special_tokens: &[String]) -> Vec<Chunk<'t>> {
+    if special_tokens.is_empty() {
+        return vec![Chunk::Text(text)];
+    }
+
+    let mut out = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let next_match = special_tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| !token.is_empty())
+            .filter_map(|(id, token)| rest.find(token.as_str()).map(|pos| (pos, token.len(), id as u32)))
+            .min_by_key(|&(pos, len, _)| (pos, std::cmp::Reverse(len)));
+
+        let Some((pos, len, id)) = next_match else {
+            out.push(Chunk::Text(rest));
+            break;
+        };
+
+        if pos > 0 {
+            out.push(Chunk::Text(&rest[..pos]));
+        }
+        out.push(Chunk::Special(id));
+        rest = &rest[pos + len..];
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trained_tokenizer() -> (Tokenizer, PreTokenizer) {
+        let pre_tokenizer = PreTokenizer::gpt2();
+        let text = "the quick brown fox jumps over the lazy dog the fox runs";
+
+        let mut word_counts: HashMap<String, i32> = HashMap::new();
+        for word in pre_tokenizer.split(text) {
+            *word_counts.entry(word.to_string()).or_default() += 1;
+        }
+
+        let trainer = crate::trainer::BpeTrainer::new(crate::trainer::BpeTrainerConfig {
+            vocab_size: 280,
+            min_frequency: 0,
+            special_tokens: vec!["<|endoftext|>".to_string()],
+            limit_alphabet: None,
+        });
+        let (mut words, counts, byte_fallback) =
+            crate::trainer::words_from_counts(&word_counts, trainer.base_offset(), trainer.limit_alphabet());
+        let merges = trainer.train(&mut words, &counts);
+
+        (Tokenizer::new(merges, trainer.special_tokens().to_vec(), byte_fallback), pre_tokenizer)
+    }
+
+    /// Encoding the same text twice must produce identical token ids.
+    #[test]
+    fn encode_is_deterministic() {
+        let (tokenizer, pre_tokenizer) = trained_tokenizer();
+        let text = "the quick fox jumps, <|endoftext|> again";
+
+        let first = tokenizer.encode(text, &pre_tokenizer);
+        let second = tokenizer.encode(text, &pre_tokenizer);
+
+        assert_eq!(first, second);
+        assert!(!first.is_empty());
+    }
+
+    /// A tokenizer saved to disk and reloaded must encode identically to the
+    /// original.
+    #[test]
+    fn save_load_round_trip_preserves_encoding() {
+        let (tokenizer, pre_tokenizer) = trained_tokenizer();
+        let text = "the quick fox jumps, <|endoftext|> again";
+        let before = tokenizer.encode(text, &pre_tokenizer);
+
+        let path = std::env::temp_dir().join("zyth_tokenizer_save_load_round_trip_test.json");
+        tokenizer.save(&path).expect("save should succeed");
+        let reloaded = Tokenizer::load(&path).expect("load should succeed");
+        std::fs::remove_file(&path).expect("cleanup should succeed");
+
+        assert_eq!(reloaded.merges(), tokenizer.merges());
+        assert_eq!(reloaded.vocab(), tokenizer.vocab());
+        assert_eq!(reloaded.encode(text, &pre_tokenizer), before);
+    }
+
+    /// `count_tokens` must agree with `encode(..).len()`.
+    #[test]
+    fn count_tokens_matches_encode_len() {
+        let (tokenizer, pre_tokenizer) = trained_tokenizer();
+        let text = "the quick fox jumps, <|endoftext|> again";
+
+        assert_eq!(tokenizer.count_tokens(text, &pre_tokenizer), tokenizer.encode(text, &pre_tokenizer).len());
+    }
+
+    /// A byte `limit_alphabet` excluded at training time must encode to the
+    /// same fallback id it was folded to during training, not its own raw
+    /// byte id -- otherwise encode disagrees with what the merges were
+    /// actually learned over.
+    #[test]
+    fn excluded_byte_encodes_as_fallback_id() {
+        let pre_tokenizer = PreTokenizer::gpt2();
+        let mut word_counts: HashMap<String, i32> = HashMap::new();
+        word_counts.insert("r".repeat(20), 100);
+        word_counts.insert("z".to_string(), 1);
+
+        let trainer = crate::trainer::BpeTrainer::new(crate::trainer::BpeTrainerConfig {
+            vocab_size: 300,
+            min_frequency: 0,
+            special_tokens: Vec::new(),
+            limit_alphabet: Some(1),
+        });
+
+        let (mut words, counts, byte_fallback) =
+            crate::trainer::words_from_counts(&word_counts, trainer.base_offset(), trainer.limit_alphabet());
+        let merges = trainer.train(&mut words, &counts);
+        let tokenizer = Tokenizer::new(merges, trainer.special_tokens().to_vec(), byte_fallback);
+
+        let (_, fallback_id) = trainer.limit_alphabet().unwrap();
+        let r_id = trainer.base_offset() + b'r' as u32;
+
+        assert_eq!(tokenizer.encode("z", &pre_tokenizer), vec![fallback_id]);
+        assert_ne!(fallback_id, r_id, "excluded byte must not alias onto a kept byte's id");
+    }
+}
@@ -0,0 +1,90 @@
+//! A single training word: a sequence of token ids that merges are applied to.
+
+use std::collections::HashMap;
+
+pub type Pair = (u32, u32);
+
+#[derive(Clone)]
+pub struct Word {
+    pub ids: Vec<u32>,
+}
+
+impl Word {
+    pub fn pairs(&self) -> impl Iterator<Item = Pair> + '_ {
+        self.ids.windows(2).map(|w| (w[0], w[1]))
+    }
+
+    /// Merges every non-overlapping occurrence of `pair` into `new_id`.
+    pub fn merge_pair(&mut self, pair: Pair, new_id: u32) {
+        let (a, b) = pair;
+        let n = self.ids.len();
+        if n < 2 {
+            return;
+        }
+
+        let mut out = Vec::with_capacity(n);
+        let mut i = 0;
+        while i < n {
+            if i + 1 < n && self.ids[i] == a && self.ids[i + 1] == b {
+                out.push(new_id);
+                i += 2;
+            } else {
+                out.push(self.ids[i]);
+                i += 1;
+            }
+        }
+        self.ids = out;
+    }
+
+    /// Same as `merge_pair`, but also reports the pair-count deltas (unweighted
+    /// by word frequency) caused by the merge: every neighbor pair whose count
+    /// in this word went up or down as a side effect.
+    ///
+    /// This compares the word's pair multiset before and after the merge
+    /// rather than inferring neighbors from each individual merge site --
+    /// a run of a self-referential pair (e.g. merging `(a, a)` in `"aaaa"`)
+    /// has merge sites whose "neighbor" is the very pair being merged, and
+    /// a per-site rule double-counts that case. Diffing the two multisets
+    /// sidesteps it: `pair` itself is excluded since the trainer already
+    /// removes its global count wholesale once all of its occurrences merge.
+    ///
+    /// Callers scale each delta by this word's frequency before folding it into
+    /// a global pair-count table, which is what lets the trainer update counts
+    /// incrementally instead of re-scanning the whole corpus per merge.
+    pub fn merge_pair_tracked(&mut self, pair: Pair, new_id: u32) -> Vec<(Pair, i32)> {
+        let before = self.ids.clone();
+        self.merge_pair(pair, new_id);
+        if before == self.ids {
+            return Vec::new();
+        }
+
+        let before_counts = pair_counts(&before);
+        let after_counts = pair_counts(&self.ids);
+
+        let mut changes = Vec::new();
+        for (&p, &count) in &before_counts {
+            if p == pair {
+                continue;
+            }
+            let delta = after_counts.get(&p).copied().unwrap_or(0) - count;
+            if delta != 0 {
+                changes.push((p, delta));
+            }
+        }
+        for (&p, &count) in &after_counts {
+            if before_counts.contains_key(&p) || p == pair {
+                continue;
+            }
+            changes.push((p, count));
+        }
+        changes
+    }
+}
+
+fn pair_counts(ids: &[u32]) -> HashMap<Pair, i32> {
+    let mut counts = HashMap::new();
+    for w in ids.windows(2) {
+        *counts.entry((w[0], w[1])).or_insert(0) += 1;
+    }
+    counts
+}